@@ -1,12 +1,61 @@
-use bevy::{core::FixedTimestep, prelude::*, sprite::collide_aabb::collide};
+use bevy::{
+    asset::{AssetLoader, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    sprite::collide_aabb::collide,
+    utils::BoxedFuture,
+};
+use bevy_ggrs::{
+    ggrs::{self, PlayerType, SessionBuilder, UdpNonBlockingSocket},
+    GGRSPlugin, GGRSSchedule, PlayerInputs, Rollback, RollbackIdProvider, Session,
+};
+// `bevy`'s own `bevy_audio` feature must stay disabled in Cargo.toml (its
+// `AudioSource`/`Audio` would otherwise collide with these) — `bevy_kira_audio`
+// is what actually gives us panning/attenuation by entity distance on this
+// Bevy generation; the built-in `bevy::audio::SpatialListener` API is from a
+// much later one.
+use bevy_kira_audio::{Audio, AudioEmitter, AudioPlugin, AudioReceiver, AudioSource, SpatialAudio};
 use rand::distributions::{Distribution, Uniform};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
 
 const SCALE: f32 = 3.0;
 const TILE_SIZE: f32 = SCALE * 16.0;
 const PLAYER_SIZE: f32 = SCALE * 32.0;
+const FIXED_DT: f32 = 1.0 / 60.0;
+const SFX_MAX_DISTANCE: f32 = TILE_SIZE * 10.0;
+
+const INPUT_UP: u8 = 1 << 0;
+const INPUT_DOWN: u8 = 1 << 1;
+const INPUT_LEFT: u8 = 1 << 2;
+const INPUT_RIGHT: u8 = 1 << 3;
+const INPUT_USE_TOOL: u8 = 1 << 4;
+const INPUT_PICKUP: u8 = 1 << 5;
+
+/// Bit-packed movement/action input sent through the GGRS rollback session.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+struct BoxInput {
+    bits: u8,
+}
+
+struct GGRSConfig;
+
+impl ggrs::Config for GGRSConfig {
+    type Input = BoxInput;
+    type State = u8;
+    type Address = SocketAddr;
+}
 
 pub struct GamePlugin;
 
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+enum AppState {
+    MainMenu,
+    Playing,
+    Paused,
+}
+
 enum CollisionLayer {
     Environment,
     Characters,
@@ -14,7 +63,17 @@ enum CollisionLayer {
 }
 
 #[derive(Component)]
-struct Player;
+struct Player {
+    handle: usize,
+}
+
+/// Tags the `Player` entity driven by this peer's own input, as opposed to a
+/// remote peer's player that the rollback session simulates alongside it.
+#[derive(Component)]
+struct LocalPlayer;
+
+#[derive(Component)]
+struct MainCamera;
 
 #[derive(Component)]
 struct Crop;
@@ -25,14 +84,42 @@ struct CollisionConfig {
     mask: u32,
 }
 
+#[derive(Component)]
+struct Collider {
+    size: Vec2,
+}
+
 #[derive(Component, Debug)]
 struct Growable {
     growth_state: u32,
     max_growth_state: u32,
+    /// Fixed 60 Hz ticks accumulated toward the next growth stage. Counting
+    /// ticks instead of `Res<Time>`/`Timer` keeps growth reproducible across
+    /// GGRS rollback re-simulation, and rides along with the rest of
+    /// `Growable` in the rollback snapshot.
+    elapsed_ticks: u32,
 }
 
 #[derive(Component)]
-struct Hydration(f32);
+struct Hydration {
+    level: f32,
+    /// Bumped once per watering action, independent of `level`. `grow_system`
+    /// resets `level` to 0 the same tick a crop advances, which would drop a
+    /// same-tick watering splash if the feedback sfx compared `level` alone;
+    /// a dedicated monotonically-increasing counter can't be stomped that way.
+    watered: u32,
+}
+
+/// Tracks the last `Growable`/`Hydration` values this peer has already played
+/// a sound effect for. Deliberately NOT a rollback component: GGRS can re-run
+/// the same confirmed tick many times during resimulation, and gating sfx on
+/// this (locally-owned, never snapshotted) tracker keeps each sound a one-shot
+/// instead of replaying it on every re-simulated frame.
+#[derive(Component)]
+struct SfxState {
+    growth_state: u32,
+    watered: u32,
+}
 
 #[derive(Component)]
 struct Animation(bool);
@@ -43,10 +130,13 @@ struct FollowTarget {
     offset: Vec3,
     flip_x: bool,
     grid_snap: bool,
+    owner: usize,
 }
 
 #[derive(Component)]
-struct PlantSeedTool; // TODO: seed type
+struct PlantSeedTool {
+    crop_id: String,
+}
 
 #[derive(Component)]
 struct WaterPlantTool; // TODO: water amount (for upgraded watering can)
@@ -54,31 +144,258 @@ struct WaterPlantTool; // TODO: water amount (for upgraded watering can)
 #[derive(Component)]
 struct Highlight;
 
+#[derive(Component)]
+struct CropKind(String);
+
+/// Tags an entity spawned for a `Playing` session so it can be despawned on exit.
+#[derive(Component)]
+struct GameplayEntity;
+
+#[derive(Component)]
+struct MainMenuUi;
+
+#[derive(Component)]
+struct PauseUi;
+
+struct FieldBounds {
+    min: Vec2,
+    max: Vec2,
+}
+
+/// Which GGRS player handles this peer owns locally vs. the total session size.
+struct PlayerHandles {
+    local: Vec<usize>,
+    total: usize,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct CropDef {
+    id: String,
+    texture: String,
+    columns: usize,
+    rows: usize,
+    max_growth_state: u32,
+    seconds_per_stage: f32,
+    hydration_per_stage: f32,
+    harvest_yield: u32,
+}
+
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "c1f7b9d2-5a3e-4b9f-9a2d-6e6f9d9a9b01"]
+struct CropManifest {
+    crops: Vec<CropDef>,
+}
+
 #[derive(Default)]
-struct TextureHandles {
-    crops: Handle<TextureAtlas>,
+struct CropManifestLoader;
+
+impl AssetLoader for CropManifestLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let manifest: CropManifest = serde_json::from_slice(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(manifest));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json"]
+    }
+}
+
+struct CropManifestHandle(Handle<CropManifest>);
+
+#[derive(Default)]
+struct CropAssets {
+    defs: HashMap<String, CropDef>,
+    atlases: HashMap<String, Handle<TextureAtlas>>,
+}
+
+/// Preloaded one-shot sound effects, so the gameplay systems that trigger
+/// them never pay a disk-load hitch mid-frame.
+struct SfxAssets {
+    plant: Handle<AudioSource>,
+    water: Handle<AudioSource>,
+    pickup: Handle<AudioSource>,
+    pop: Handle<AudioSource>,
 }
 
-const TIME_STEP: f32 = 1.0 / 60.0;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(TextureHandles {
+        GGRSPlugin::<GGRSConfig>::new()
+            .with_update_frequency(60)
+            .with_input_system(ggrs_input_system)
+            .register_rollback_component::<Transform>()
+            .register_rollback_component::<Growable>()
+            .register_rollback_component::<Hydration>()
+            .register_rollback_component::<FollowTarget>()
+            .register_rollback_component::<CropKind>()
+            .build(app);
+
+        app.add_plugin(AudioPlugin)
+            .insert_resource(SpatialAudio {
+                max_distance: SFX_MAX_DISTANCE,
+            })
+            .add_state(AppState::MainMenu)
+            .insert_resource(CropAssets::default())
+            .add_asset::<CropManifest>()
+            .init_asset_loader::<CropManifestLoader>()
+            .add_startup_system(setup_ui_camera)
+            .add_startup_system(setup_crop_manifest)
+            .add_startup_system(setup_sfx_assets)
+            .add_system(load_crop_defs_system)
+            .add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(setup_main_menu))
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(main_menu_system))
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu).with_system(despawn_with::<MainMenuUi>),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(spawn_players)
+                    .with_system(setup_world)
+                    .with_system(setup_tiles),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(animate_sprite_system)
+                    .with_system(update_follow_system)
+                    .with_system(follow_system)
+                    .with_system(camera_follow_system)
+                    .with_system(pause_input_system)
+                    .with_system(crop_planted_sfx_system)
+                    .with_system(crop_feedback_sfx_system)
+                    .with_system(tool_pickup_sfx_system),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Playing).with_system(despawn_with::<GameplayEntity>),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Paused).with_system(setup_pause_overlay),
+            )
+            .add_system_set(SystemSet::on_update(AppState::Paused).with_system(pause_menu_system))
+            .add_system_set(
+                SystemSet::on_exit(AppState::Paused).with_system(despawn_with::<PauseUi>),
+            )
+            // NOTE: gating on local `AppState` only stops *this* peer's
+            // simulation from stepping. A GGRS P2P session has no concept of
+            // a synchronized pause, so the remote peer keeps advancing while
+            // this one sits in `Paused`; resuming will desync the rollback
+            // state. Pausing is therefore single-player-only until pause is
+            // threaded through `BoxInput` as a synchronized button instead of
+            // a local run criterion.
+            .add_system_set_to_schedule(
+                GGRSSchedule,
+                SystemSet::new()
+                    .with_run_criteria(State::on_update(AppState::Playing))
+                    .with_system(player_movement_system)
+                    .with_system(grow_system)
+                    .with_system(use_tool_system)
+                    .with_system(pickup_system),
+            );
+    }
+}
+
+fn despawn_with<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn setup_ui_camera(mut commands: Commands) {
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+fn setup_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::Center,
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "Crop Time\n\nPress Enter to Start",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 50.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment {
+                    horizontal: HorizontalAlign::Center,
+                    ..Default::default()
+                },
+            ),
             ..Default::default()
         })
-        .add_startup_system(setup_player)
-        .add_startup_system(setup_tiles)
-        .add_startup_system(setup_crop_textures)
-        .add_system(animate_sprite_system)
-        .add_system(grow_system)
-        .add_system(update_follow_system)
-        .add_system(follow_system)
-        .add_system(use_tool_system)
-        .add_system(pickup_system)
-        .add_system_set(
-            SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(TIME_STEP as f64))
-                .with_system(player_movement_system),
-        );
+        .insert(MainMenuUi);
+}
+
+/// Requires `CropAssets` to be populated before leaving `MainMenu`: peers can
+/// finish loading `crops.json` at different times, and entering `Playing`
+/// before it's loaded lets one peer spawn a crop in `use_tool_system` while
+/// the other silently no-ops, diverging the rollback simulation.
+fn main_menu_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    crop_assets: Res<CropAssets>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if crop_assets.defs.is_empty() {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        app_state.set(AppState::Playing).unwrap();
+    }
+}
+
+/// Single-player-only: pausing only stops the local `AppState`, and a GGRS
+/// P2P peer has no way to tell the remote side to stop advancing too, so
+/// pausing during a netplay session will desync the rollback simulation on
+/// resume.
+fn pause_input_system(keyboard_input: Res<Input<KeyCode>>, mut app_state: ResMut<State<AppState>>) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        app_state.push(AppState::Paused).unwrap();
+    }
+}
+
+fn setup_pause_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(PauseUi)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "Paused\n\nPress Escape to Resume",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        ..Default::default()
+                    },
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+fn pause_menu_system(keyboard_input: Res<Input<KeyCode>>, mut app_state: ResMut<State<AppState>>) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        app_state.pop().unwrap();
     }
 }
 
@@ -106,23 +423,32 @@ fn animate_sprite_system(
 }
 
 fn grow_system(
-    time: Res<Time>,
+    crop_assets: Res<CropAssets>,
     //mut commands: Commands,
     mut query: Query<(
         Entity,
-        &mut Timer,
         &mut TextureAtlasSprite,
         &mut Growable,
         &mut Hydration,
+        &CropKind,
     )>,
 ) {
-    for (_entity, mut timer, mut sprite, mut growable, mut hydration) in query.iter_mut() {
-        timer.tick(time.delta());
-        if timer.finished() {
-            if growable.growth_state < growable.max_growth_state && hydration.0 >= 1.0 {
+    for (_entity, mut sprite, mut growable, mut hydration, crop_kind) in query.iter_mut() {
+        let crop_def = crop_assets.defs.get(&crop_kind.0);
+        let hydration_per_stage = crop_def.map(|def| def.hydration_per_stage).unwrap_or(1.0);
+        let ticks_per_stage = crop_def
+            .map(|def| ((def.seconds_per_stage / FIXED_DT).round() as u32).max(1))
+            .unwrap_or(1);
+
+        growable.elapsed_ticks += 1;
+        if growable.elapsed_ticks >= ticks_per_stage {
+            growable.elapsed_ticks = 0;
+            if growable.growth_state < growable.max_growth_state
+                && hydration.level >= hydration_per_stage
+            {
                 growable.growth_state += 1;
                 sprite.index = growable.growth_state as usize;
-                hydration.0 = 0.0;
+                hydration.level = 0.0;
                 //} else {
                 //    commands.entity(entity).remove::<Growable>();
             }
@@ -142,17 +468,48 @@ fn action_pressed(name: &str, keyboard_input: &Res<Input<KeyCode>>) -> bool {
     }
 }
 
+/// Packs this frame's local keyboard state into the bits GGRS ships to every
+/// peer; `player_movement_system` and friends only ever read `BoxInput`, never
+/// `Input<KeyCode>` directly, so replayed/rolled-back frames stay deterministic.
+fn ggrs_input_system(In(_handle): In<ggrs::PlayerHandle>, keyboard_input: Res<Input<KeyCode>>) -> BoxInput {
+    let mut bits = 0u8;
+    if action_pressed("move_up", &keyboard_input) {
+        bits |= INPUT_UP;
+    }
+    if action_pressed("move_down", &keyboard_input) {
+        bits |= INPUT_DOWN;
+    }
+    if action_pressed("move_left", &keyboard_input) {
+        bits |= INPUT_LEFT;
+    }
+    if action_pressed("move_right", &keyboard_input) {
+        bits |= INPUT_RIGHT;
+    }
+    if keyboard_input.pressed(KeyCode::Return) {
+        bits |= INPUT_USE_TOOL;
+    }
+    if keyboard_input.pressed(KeyCode::E) {
+        bits |= INPUT_PICKUP;
+    }
+
+    BoxInput { bits }
+}
+
 fn update_follow_system(
-    query: Query<(&TextureAtlasSprite, &Transform), With<Player>>,
+    player_query: Query<(&Player, &TextureAtlasSprite, &Transform)>,
     mut fq: Query<&mut FollowTarget>,
 ) {
-    let (sprite, transform) = query.single();
-    for mut follow in fq.iter_mut() {
-        follow.target = transform.translation;
-        follow.target.y += PLAYER_SIZE / 4.0;
-        follow.target.x += PLAYER_SIZE / 4.0;
-        follow.target.z = 1.0;
-        follow.flip_x = sprite.flip_x;
+    for (player, sprite, transform) in player_query.iter() {
+        for mut follow in fq.iter_mut() {
+            if follow.owner != player.handle {
+                continue;
+            }
+            follow.target = transform.translation;
+            follow.target.y += PLAYER_SIZE / 4.0;
+            follow.target.x += PLAYER_SIZE / 4.0;
+            follow.target.z = 1.0;
+            follow.flip_x = sprite.flip_x;
+        }
     }
 }
 
@@ -173,22 +530,151 @@ fn follow_system(mut query: Query<(&mut Transform, &FollowTarget)>) {
     }
 }
 
+fn camera_follow_system(
+    time: Res<Time>,
+    field_bounds: Option<Res<FieldBounds>>,
+    windows: Res<Windows>,
+    player_query: Query<&Transform, (With<LocalPlayer>, Without<MainCamera>)>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+) {
+    // `setup_tiles` inserts `FieldBounds` via a deferred command in the same
+    // `on_enter(Playing)` stage this system's `on_update(Playing)` runs in, so
+    // it isn't visible yet on the very first `Playing` frame. Skip a tick
+    // rather than panic on the missing resource.
+    let field_bounds = match field_bounds {
+        Some(field_bounds) => field_bounds,
+        None => return,
+    };
+
+    let player_transform = player_query.single();
+    let (mut camera_transform, projection) = camera_query.single_mut();
+
+    let window = get_primary_window_size(windows);
+    let half_view = window / 2.0 * projection.scale;
+    let field_size = field_bounds.max - field_bounds.min;
+
+    let mut target = player_transform.translation.truncate();
+    target.x = if field_size.x < window.x * projection.scale {
+        (field_bounds.min.x + field_bounds.max.x) / 2.0
+    } else {
+        target
+            .x
+            .clamp(field_bounds.min.x + half_view.x, field_bounds.max.x - half_view.x)
+    };
+    target.y = if field_size.y < window.y * projection.scale {
+        (field_bounds.min.y + field_bounds.max.y) / 2.0
+    } else {
+        target
+            .y
+            .clamp(field_bounds.min.y + half_view.y, field_bounds.max.y - half_view.y)
+    };
+
+    let smoothing = 1.0 - (-10.0 * time.delta_seconds()).exp();
+    let smoothed = camera_transform.translation.truncate().lerp(target, smoothing);
+    camera_transform.translation.x = smoothed.x;
+    camera_transform.translation.y = smoothed.y;
+}
+
+/// The tile a player is facing, computed the same way the cursor `Highlight`
+/// places itself in `follow_system` so tool use stays in sync with what's shown.
+fn interaction_tile(player_transform: &Transform, flip_x: bool) -> Vec3 {
+    let mut target = player_transform.translation;
+    target.y += PLAYER_SIZE / 4.0;
+    target.x += PLAYER_SIZE / 4.0;
+    target.z = 1.0;
+
+    let flip = if flip_x { -1.0 } else { 1.0 };
+    let player_size = 32.0;
+    let offset = Vec3::new(flip * (-player_size / 2.0 * SCALE) - TILE_SIZE / 2.0, 0.0, 0.0);
+    pixel_to_tile_coord(target + offset)
+}
+
+/// Plays `source`, spatialized against whichever entity holds `AudioReceiver`
+/// (the local `Player`) by `emitter`'s own `Transform` — `SpatialAudio`
+/// (inserted in `GamePlugin::build`) handles the distance-based attenuation
+/// every frame, so callers just hand it the instance to track.
+fn play_sfx(audio: &Audio, source: &Handle<AudioSource>, emitter: &mut AudioEmitter) {
+    emitter.instances.push(audio.play(source.clone()).handle());
+}
+
+/// Plays the planting sound once per newly spawned `Crop`. Runs in the
+/// regular `on_update(Playing)` set rather than `GGRSSchedule`, so GGRS
+/// rollback re-simulating a tick never replays this more than once.
+fn crop_planted_sfx_system(
+    sfx: Res<SfxAssets>,
+    audio: Res<Audio>,
+    mut query: Query<&mut AudioEmitter, Added<Crop>>,
+) {
+    for mut emitter in query.iter_mut() {
+        play_sfx(&audio, &sfx.plant, &mut emitter);
+    }
+}
+
+/// Plays the growth-pop sound by comparing each crop's current
+/// `Growable::growth_state`, and the watering splash by comparing
+/// `Hydration::watered`, against the last values `SfxState` saw. Both are
+/// monotonically-increasing counters rather than raw levels, so a watering
+/// and a growth tick landing in the same frame (which resets
+/// `Hydration::level` back to 0) can't stomp each other's signal. Like
+/// `crop_planted_sfx_system`, this lives outside `GGRSSchedule` so it only
+/// reacts once per rendered frame instead of once per rollback replay.
+fn crop_feedback_sfx_system(
+    sfx: Res<SfxAssets>,
+    audio: Res<Audio>,
+    mut query: Query<(&Growable, &Hydration, &mut SfxState, &mut AudioEmitter)>,
+) {
+    for (growable, hydration, mut state, mut emitter) in query.iter_mut() {
+        if growable.growth_state > state.growth_state {
+            state.growth_state = growable.growth_state;
+            play_sfx(&audio, &sfx.pop, &mut emitter);
+        }
+        if hydration.watered > state.watered {
+            state.watered = hydration.watered;
+            play_sfx(&audio, &sfx.water, &mut emitter);
+        }
+    }
+}
+
+/// Plays the pickup sound once per tool pickup (a `FollowTarget`
+/// newly attached to a carried tool). Excludes `Highlight`, whose
+/// `FollowTarget` is set up once at `setup_world` and never "picked up".
+fn tool_pickup_sfx_system(
+    sfx: Res<SfxAssets>,
+    audio: Res<Audio>,
+    mut query: Query<&mut AudioEmitter, (Added<FollowTarget>, Without<Highlight>)>,
+) {
+    for mut emitter in query.iter_mut() {
+        play_sfx(&audio, &sfx.pickup, &mut emitter);
+    }
+}
+
 fn use_tool_system(
     mut commands: Commands,
-    texture_handles: Res<TextureHandles>,
-    keyboard_input: Res<Input<KeyCode>>,
-    query: Query<&Transform, With<Highlight>>,
-    tool_query: Query<(Option<&WaterPlantTool>, Option<&PlantSeedTool>), With<FollowTarget>>,
-    mut cq: Query<(&Transform, &mut Hydration), With<Crop>>,
+    mut rip: ResMut<RollbackIdProvider>,
+    crop_assets: Res<CropAssets>,
+    inputs: Res<PlayerInputs<GGRSConfig>>,
+    player_query: Query<(&Player, &Transform, &TextureAtlasSprite)>,
+    tool_query: Query<(&FollowTarget, Option<&WaterPlantTool>, Option<&PlantSeedTool>)>,
+    mut cq: Query<(&Transform, &mut Hydration, &CropKind), With<Crop>>,
 ) {
-    for (water_tool, plant_tool) in tool_query.iter() {
-        if plant_tool.is_some() {
-            let transform = query.single();
-            if keyboard_input.just_pressed(KeyCode::Return) {
+    for (player, player_transform, sprite) in player_query.iter() {
+        let (input, _) = inputs[player.handle];
+        if input.bits & INPUT_USE_TOOL == 0 {
+            continue;
+        }
+
+        let target = interaction_tile(player_transform, sprite.flip_x);
+
+        for (follow, water_tool, plant_tool) in tool_query.iter() {
+            if follow.owner != player.handle {
+                continue;
+            }
+
+            if let Some(plant_tool) = plant_tool {
                 let mut free_slot = true;
-                for (crop_tf, _) in cq.iter() {
+                for (crop_tf, _, _) in cq.iter() {
                     let crop = Vec3::new(crop_tf.translation.x, crop_tf.translation.y, 0.0);
-                    let new_crop = Vec3::new(transform.translation.x, transform.translation.y, 0.0);
+                    let new_crop = Vec3::new(target.x, target.y, 0.0);
                     if crop == new_crop {
                         free_slot = false;
                         break;
@@ -196,35 +682,53 @@ fn use_tool_system(
                 }
 
                 if free_slot {
-                    commands
-                        .spawn_bundle(SpriteSheetBundle {
-                            texture_atlas: texture_handles.crops.to_owned(),
-                            transform: Transform {
-                                translation: transform.translation,
-                                scale: Vec3::splat(SCALE),
+                    if let (Some(def), Some(atlas_handle)) = (
+                        crop_assets.defs.get(&plant_tool.crop_id),
+                        crop_assets.atlases.get(&plant_tool.crop_id),
+                    ) {
+                        commands
+                            .spawn_bundle(SpriteSheetBundle {
+                                texture_atlas: atlas_handle.to_owned(),
+                                transform: Transform {
+                                    translation: target,
+                                    scale: Vec3::splat(SCALE),
+                                    ..Default::default()
+                                },
                                 ..Default::default()
-                            },
-                            ..Default::default()
-                        })
-                        .insert(Growable {
-                            growth_state: 0,
-                            max_growth_state: 2,
-                        })
-                        .insert(Hydration(0.0))
-                        .insert(Crop)
-                        .insert(Timer::from_seconds(5.0, true));
+                            })
+                            .insert(Growable {
+                                growth_state: 0,
+                                max_growth_state: def.max_growth_state,
+                                elapsed_ticks: 0,
+                            })
+                            .insert(Hydration {
+                                level: 0.0,
+                                watered: 0,
+                            })
+                            .insert(Crop)
+                            .insert(CropKind(plant_tool.crop_id.clone()))
+                            .insert(SfxState {
+                                growth_state: 0,
+                                watered: 0,
+                            })
+                            .insert(AudioEmitter::default())
+                            .insert(Rollback::new(rip.next_id()));
+                    }
                 }
             }
-        }
 
-        if water_tool.is_some() {
-            if keyboard_input.just_pressed(KeyCode::Return) {
-                let transform = query.single();
-                for (crop_tf, mut hydration) in cq.iter_mut() {
+            if water_tool.is_some() {
+                for (crop_tf, mut hydration, crop_kind) in cq.iter_mut() {
                     let crop = Vec3::new(crop_tf.translation.x, crop_tf.translation.y, 0.0);
-                    let new_crop = Vec3::new(transform.translation.x, transform.translation.y, 0.0);
+                    let new_crop = Vec3::new(target.x, target.y, 0.0);
                     if crop == new_crop {
-                        hydration.0 = 1.0;
+                        let hydration_per_stage = crop_assets
+                            .defs
+                            .get(&crop_kind.0)
+                            .map(|def| def.hydration_per_stage)
+                            .unwrap_or(1.0);
+                        hydration.level = hydration_per_stage;
+                        hydration.watered += 1;
                     }
                 }
             }
@@ -234,13 +738,17 @@ fn use_tool_system(
 
 fn pickup_system(
     mut commands: Commands,
-    keyboard_input: Res<Input<KeyCode>>,
-    player_query: Query<(&Transform, &CollisionConfig), With<Player>>,
+    inputs: Res<PlayerInputs<GGRSConfig>>,
+    player_query: Query<(&Player, &Transform, &CollisionConfig)>,
     col_query: Query<(Entity, &Transform, &CollisionConfig), Without<FollowTarget>>,
-    tool_query: Query<Entity, (With<FollowTarget>, Without<Highlight>)>,
+    tool_query: Query<(Entity, &FollowTarget), Without<Highlight>>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::E) {
-        let (player_transform, player_col_config) = player_query.single();
+    for (player, player_transform, player_col_config) in player_query.iter() {
+        let (input, _) = inputs[player.handle];
+        if input.bits & INPUT_PICKUP == 0 {
+            continue;
+        }
+
         for (entity, transform, collision_config) in col_query.iter() {
             let collision = collide(
                 player_transform.translation,
@@ -252,49 +760,100 @@ fn pickup_system(
                 && collision_config.layer & player_col_config.mask != 0
                 && collision_config.layer & CollisionLayer::Tools as u32 != 0
             {
-                for active_tool in tool_query.iter() {
-                    commands.entity(active_tool).remove::<FollowTarget>();
+                for (active_tool, follow) in tool_query.iter() {
+                    if follow.owner == player.handle {
+                        commands.entity(active_tool).remove::<FollowTarget>();
+                    }
                 }
                 commands.entity(entity).insert(FollowTarget {
                     target: transform.translation,
                     offset: Vec3::new(-TILE_SIZE / 3.0 * SCALE, -TILE_SIZE / 2.0, 0.0),
                     flip_x: false,
                     grid_snap: false,
+                    owner: player.handle,
                 });
             }
         }
     }
 }
 
+fn environment_collision(
+    translation: Vec3,
+    col_query: &Query<(&Transform, &Collider, &CollisionConfig), Without<Player>>,
+) -> bool {
+    for (transform, collider, collision_config) in col_query.iter() {
+        // `CollisionLayer::Environment as u32` is 0, so it cannot be tested
+        // with a bitwise `&` against a mask; compare the layer by value.
+        if collision_config.layer != CollisionLayer::Environment as u32 {
+            continue;
+        }
+        if collide(
+            translation,
+            Vec2::splat(PLAYER_SIZE),
+            transform.translation,
+            collider.size,
+        )
+        .is_some()
+        {
+            return true;
+        }
+    }
+    false
+}
+
 fn player_movement_system(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&mut Transform, &mut TextureAtlasSprite, &mut Animation), With<Player>>,
+    inputs: Res<PlayerInputs<GGRSConfig>>,
+    mut player_query: Query<(
+        &mut Transform,
+        &mut TextureAtlasSprite,
+        &mut Animation,
+        &CollisionConfig,
+        &Player,
+    )>,
+    col_query: Query<(&Transform, &Collider, &CollisionConfig), Without<Player>>,
 ) {
-    let (mut transform, mut sprite, mut animation) = query.single_mut();
-    let mut direction = Vec3::ZERO;
-    if action_pressed("move_left", &keyboard_input) {
-        direction.x -= 1.0;
-        sprite.flip_x = false;
-    }
-    if action_pressed("move_right", &keyboard_input) {
-        direction.x += 1.0;
-        sprite.flip_x = true;
-    }
-    if action_pressed("move_up", &keyboard_input) {
-        direction.y += 1.0;
-    }
-    if action_pressed("move_down", &keyboard_input) {
-        direction.y -= 1.0;
-    }
+    for (mut transform, mut sprite, mut animation, _, player) in player_query.iter_mut() {
+        let (input, _) = inputs[player.handle];
 
-    animation.0 = direction.length() > 0.1;
+        let mut direction = Vec3::ZERO;
+        if input.bits & INPUT_LEFT != 0 {
+            direction.x -= 1.0;
+            sprite.flip_x = false;
+        }
+        if input.bits & INPUT_RIGHT != 0 {
+            direction.x += 1.0;
+            sprite.flip_x = true;
+        }
+        if input.bits & INPUT_UP != 0 {
+            direction.y += 1.0;
+        }
+        if input.bits & INPUT_DOWN != 0 {
+            direction.y -= 1.0;
+        }
 
-    let translation = &mut transform.translation;
-    *translation += direction.normalize_or_zero() * 250.0 * TIME_STEP;
+        animation.0 = direction.length() > 0.1;
+
+        let movement = direction.normalize_or_zero() * 250.0 * FIXED_DT;
+
+        let mut new_translation = transform.translation;
+        new_translation.x += movement.x;
+        if environment_collision(new_translation, &col_query) {
+            new_translation.x = transform.translation.x;
+        }
+        new_translation.y += movement.y;
+        if environment_collision(new_translation, &col_query) {
+            new_translation.y = transform.translation.y;
+        }
+        transform.translation = new_translation;
+    }
 }
 
-fn setup_player(
+/// Spawns one `Player` per slot in the GGRS session (local and remote alike)
+/// so rollback re-simulation drives every peer's character deterministically.
+fn spawn_players(
     mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    player_handles: Res<PlayerHandles>,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
@@ -302,25 +861,50 @@ fn setup_player(
     let player_texture_atlas =
         TextureAtlas::from_grid(player_texture_handle, Vec2::new(32.0, 32.0), 4, 1);
     let player_texture_atlas_handle = texture_atlases.add(player_texture_atlas);
-    let player_size = 32.0;
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-    commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: player_texture_atlas_handle,
+
+    for handle in 0..player_handles.total {
+        let spawn_offset = handle as f32 * PLAYER_SIZE * 2.0;
+        let mut player = commands.spawn_bundle(SpriteSheetBundle {
+            texture_atlas: player_texture_atlas_handle.clone(),
             transform: Transform {
-                translation: Vec3::new(0.0, 0.0, 5.0),
+                translation: Vec3::new(spawn_offset, 0.0, 5.0),
                 scale: Vec3::splat(SCALE),
                 ..Default::default()
             },
             ..Default::default()
-        })
-        .insert(Timer::from_seconds(0.1, true))
-        .insert(Animation(false))
-        .insert(CollisionConfig {
-            layer: CollisionLayer::Characters as u32,
-            mask: CollisionLayer::Environment as u32 | CollisionLayer::Tools as u32,
-        })
-        .insert(Player);
+        });
+        player
+            .insert(Timer::from_seconds(0.1, true))
+            .insert(Animation(false))
+            .insert(CollisionConfig {
+                layer: CollisionLayer::Characters as u32,
+                mask: CollisionLayer::Environment as u32 | CollisionLayer::Tools as u32,
+            })
+            .insert(Player { handle })
+            .insert(Rollback::new(rip.next_id()))
+            .insert(GameplayEntity);
+
+        if player_handles.local.contains(&handle) {
+            player.insert(LocalPlayer).insert(AudioReceiver);
+        }
+    }
+}
+
+/// Spawns the camera, tool cursor, and tool pickups shared by the session —
+/// entities that exist once per client, not once per `Player`.
+fn setup_world(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    player_handles: Res<PlayerHandles>,
+) {
+    let player_size = 32.0;
+    let local_handle = player_handles.local.first().copied().unwrap_or(0);
+
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera)
+        .insert(GameplayEntity);
+
     commands
         .spawn_bundle(SpriteBundle {
             texture: asset_server.load("highlight.png"),
@@ -335,8 +919,10 @@ fn setup_player(
             offset: Vec3::new(-player_size / 2.0 * SCALE, 0.0, 0.0),
             flip_x: false,
             grid_snap: true,
+            owner: local_handle,
         })
-        .insert(Highlight);
+        .insert(Highlight)
+        .insert(GameplayEntity);
 
     commands
         .spawn_bundle(SpriteBundle {
@@ -352,7 +938,11 @@ fn setup_player(
             layer: CollisionLayer::Tools as u32,
             mask: 0,
         })
-        .insert(PlantSeedTool);
+        .insert(PlantSeedTool {
+            crop_id: "flower".to_string(),
+        })
+        .insert(AudioEmitter::default())
+        .insert(GameplayEntity);
 
     commands
         .spawn_bundle(SpriteBundle {
@@ -368,19 +958,52 @@ fn setup_player(
             layer: CollisionLayer::Tools as u32,
             mask: 0,
         })
-        .insert(WaterPlantTool);
+        .insert(WaterPlantTool)
+        .insert(AudioEmitter::default())
+        .insert(GameplayEntity);
+}
+
+fn setup_crop_manifest(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let manifest_handle: Handle<CropManifest> = asset_server.load("crops.json");
+    commands.insert_resource(CropManifestHandle(manifest_handle));
+}
+
+fn setup_sfx_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SfxAssets {
+        plant: asset_server.load("sounds/plant.ogg"),
+        water: asset_server.load("sounds/water.ogg"),
+        pickup: asset_server.load("sounds/pickup.ogg"),
+        pop: asset_server.load("sounds/pop.ogg"),
+    });
 }
 
-fn setup_crop_textures(
+fn load_crop_defs_system(
+    mut events: EventReader<AssetEvent<CropManifest>>,
+    manifests: Res<Assets<CropManifest>>,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    mut texture_handles: ResMut<TextureHandles>,
+    mut crop_assets: ResMut<CropAssets>,
 ) {
-    let texture_handle = asset_server.load("corn.png");
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(16.0, 16.0), 4, 1);
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
-
-    texture_handles.crops = texture_atlas_handle;
+    for event in events.iter() {
+        if let AssetEvent::Created { handle } = event {
+            let manifest = match manifests.get(handle) {
+                Some(manifest) => manifest,
+                None => continue,
+            };
+            for def in &manifest.crops {
+                let texture_handle = asset_server.load(def.texture.as_str());
+                let atlas = TextureAtlas::from_grid(
+                    texture_handle,
+                    Vec2::new(16.0, 16.0),
+                    def.columns,
+                    def.rows,
+                );
+                let atlas_handle = texture_atlases.add(atlas);
+                crop_assets.atlases.insert(def.id.clone(), atlas_handle);
+                crop_assets.defs.insert(def.id.clone(), def.clone());
+            }
+        }
+    }
 }
 
 fn setup_tiles(
@@ -416,7 +1039,7 @@ fn setup_tiles(
                 2
             };
 
-            commands.spawn_bundle(SpriteSheetBundle {
+            let mut tile = commands.spawn_bundle(SpriteSheetBundle {
                 texture_atlas: tiles_texture_atlas_handle.to_owned(),
                 transform: Transform {
                     translation: position,
@@ -429,8 +1052,62 @@ fn setup_tiles(
                 },
                 ..Default::default()
             });
+            tile.insert(GameplayEntity);
+
+            // Sprite index 2 is the rare, impassable tile (e.g. a boulder).
+            if sprite_id == 2 {
+                tile.insert(Collider {
+                    size: Vec2::splat(TILE_SIZE),
+                })
+                .insert(CollisionConfig {
+                    layer: CollisionLayer::Environment as u32,
+                    mask: 0,
+                });
+            }
         }
     }
+
+    let field_min = Vec2::new(-window.x / 2.0, -window.y / 2.0);
+    let field_max = field_min + Vec2::new(columns as f32 * TILE_SIZE, rows as f32 * TILE_SIZE);
+
+    let wall_thickness = TILE_SIZE;
+    let field_size = field_max - field_min;
+    let walls = [
+        (
+            Vec3::new(field_min.x - wall_thickness / 2.0, (field_min.y + field_max.y) / 2.0, 0.0),
+            Vec2::new(wall_thickness, field_size.y + wall_thickness * 2.0),
+        ),
+        (
+            Vec3::new(field_max.x + wall_thickness / 2.0, (field_min.y + field_max.y) / 2.0, 0.0),
+            Vec2::new(wall_thickness, field_size.y + wall_thickness * 2.0),
+        ),
+        (
+            Vec3::new((field_min.x + field_max.x) / 2.0, field_min.y - wall_thickness / 2.0, 0.0),
+            Vec2::new(field_size.x + wall_thickness * 2.0, wall_thickness),
+        ),
+        (
+            Vec3::new((field_min.x + field_max.x) / 2.0, field_max.y + wall_thickness / 2.0, 0.0),
+            Vec2::new(field_size.x + wall_thickness * 2.0, wall_thickness),
+        ),
+    ];
+    for (position, size) in walls {
+        commands
+            .spawn_bundle((
+                Transform::from_translation(position),
+                GlobalTransform::default(),
+            ))
+            .insert(Collider { size })
+            .insert(CollisionConfig {
+                layer: CollisionLayer::Environment as u32,
+                mask: 0,
+            })
+            .insert(GameplayEntity);
+    }
+
+    commands.insert_resource(FieldBounds {
+        min: field_min,
+        max: field_max,
+    });
 }
 
 fn get_primary_window_size(windows: Res<Windows>) -> Vec2 {
@@ -445,7 +1122,85 @@ fn pixel_to_tile_coord(pos: Vec3) -> Vec3 {
     Vec3::new(tile_pos.x, tile_pos.y, pos.z)
 }
 
+/// Addresses of every player in the session, parsed from `--players`; the
+/// literal entry `"localhost"` marks this peer's own slot.
+struct NetworkOpts {
+    local_port: u16,
+    players: Vec<String>,
+}
+
+/// Reads `--local-port <port>` and `--players <addr-or-localhost,...>` from
+/// the command line. Exactly one entry of `players` must be `"localhost"`.
+fn parse_network_opts() -> NetworkOpts {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut local_port = 7000;
+    let mut players = vec!["localhost".to_string()];
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--local-port" => {
+                if let Some(value) = args.get(i + 1) {
+                    local_port = value.parse().expect("--local-port must be a valid u16");
+                }
+                i += 2;
+            }
+            "--players" => {
+                if let Some(value) = args.get(i + 1) {
+                    players = value.split(',').map(|s| s.to_string()).collect();
+                }
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+
+    NetworkOpts { local_port, players }
+}
+
+/// Builds the P2P rollback session described by `opts`, binding a UDP socket
+/// on `local_port` and registering one `PlayerType` per entry in `players`.
+fn create_ggrs_session(opts: &NetworkOpts) -> Session<GGRSConfig> {
+    let mut builder = SessionBuilder::<GGRSConfig>::new()
+        .with_num_players(opts.players.len())
+        .with_input_delay(2);
+
+    for (handle, player) in opts.players.iter().enumerate() {
+        let player_type = if player == "localhost" {
+            PlayerType::Local
+        } else {
+            PlayerType::Remote(player.parse().expect("invalid socket address in --players"))
+        };
+        builder = builder
+            .add_player(player_type, handle)
+            .expect("failed to add player to GGRS session");
+    }
+
+    let socket = UdpNonBlockingSocket::bind_to_port(opts.local_port)
+        .expect("failed to bind GGRS UDP socket");
+    Session::P2PSession(
+        builder
+            .start_p2p_session(socket)
+            .expect("failed to start GGRS P2P session"),
+    )
+}
+
 fn main() {
+    let opts = parse_network_opts();
+    let local: Vec<usize> = opts
+        .players
+        .iter()
+        .enumerate()
+        .filter(|(_, player)| player.as_str() == "localhost")
+        .map(|(handle, _)| handle)
+        .collect();
+    let player_handles = PlayerHandles {
+        total: opts.players.len(),
+        local,
+    };
+    let session = create_ggrs_session(&opts);
+
     App::new()
         .insert_resource(WindowDescriptor {
             title: "Crop Time".to_string(),
@@ -454,6 +1209,8 @@ fn main() {
             vsync: true,
             ..Default::default()
         })
+        .insert_resource(player_handles)
+        .insert_resource(session)
         .add_plugins(DefaultPlugins)
         .add_plugin(GamePlugin)
         .run();